@@ -0,0 +1,35 @@
+use std::io;
+
+/// An error in decoding a message or struct.
+#[derive(Debug)]
+pub enum DecodeError {
+	/// A version byte specified something we don't know how to handle, eg a version of a
+	/// serialized object which is too new for this code to read, or an unknown realm byte in an
+	/// OnionHopData packet
+	UnknownVersion,
+	/// Unknown feature mandated by a writer
+	UnknownRequiredFeature,
+	/// Value was invalid, eg a byte which was supposed to be a bool was something other than a
+	/// 0 or 1, a length descriptor included a non-minimal encoding, etc
+	InvalidValue,
+	/// Buffer too short
+	ShortRead,
+	/// A length descriptor in the packet didn't match the actual length
+	BadLengthDescriptor,
+	/// Error from an invalid public key (eg invalid curve point or impossible parity)
+	BadPublicKey,
+	/// Error from an invalid signature
+	BadSignature,
+	/// Error from std::io
+	Io(io::Error),
+}
+
+impl From<io::Error> for DecodeError {
+	fn from(e: io::Error) -> Self {
+		if e.kind() == io::ErrorKind::UnexpectedEof {
+			DecodeError::ShortRead
+		} else {
+			DecodeError::Io(e)
+		}
+	}
+}
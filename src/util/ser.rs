@@ -35,10 +35,34 @@ impl<W: ::std::io::Write> Writer for W {
 	fn size_hint(&mut self, _size: usize) { }
 }
 
+/// A Writer which does no actual writing, instead just keeping a running count of how many bytes
+/// would have been written, used to implement `Writeable::serialized_length` without allocating
+/// an intermediate buffer.
+pub(crate) struct LengthCalculatingWriter(pub usize);
+impl Writer for LengthCalculatingWriter {
+	#[inline]
+	fn write_all(&mut self, buf: &[u8]) -> Result<(), ::std::io::Error> {
+		self.0 += buf.len();
+		Ok(())
+	}
+	#[inline]
+	fn size_hint(&mut self, _size: usize) { }
+}
+
 /// A trait that various rust-lightning types implement allowing them to be written out to a Writer
-pub trait Writeable<W: Writer> {
+pub trait Writeable {
 	/// Writes self out to the given Writer
-	fn write(&self, writer: &mut W) -> Result<(), DecodeError>;
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), DecodeError>;
+
+	/// Writes self out to a length-tracking Writer solely to learn its serialized length.
+	/// Implementors with a cheaper way to know their own size (e.g. a fixed-width encoding, or a
+	/// container that can sum its elements' lengths) should override this.
+	#[inline]
+	fn serialized_length(&self) -> usize {
+		let mut len_calc = LengthCalculatingWriter(0);
+		self.write(&mut len_calc).expect("Failed to calculate length");
+		len_calc.0
+	}
 }
 
 /// A trait that various rust-lightning types implement allowing them to be read in from a Read
@@ -48,13 +72,19 @@ pub trait Readable<R>
 {
 	/// Reads a Self in from the given Read
 	fn read(reader: &mut R) -> Result<Self, DecodeError>;
+
+	/// The fewest bytes a single instance of this type can possibly take up when serialized.
+	/// `Vec<Self>`'s `Readable` impl uses this to sanity-check a declared element count against
+	/// `MAX_BUF_SIZE` before allocating space for it, so a hostile length descriptor can't trigger
+	/// an enormous `Vec::with_capacity` ahead of actually reading (and thus bounding) the data.
+	const MIN_LENGTH: usize = 1;
 }
 
 macro_rules! impl_writeable_primitive {
 	($val_type:ty, $meth_write:ident, $len: expr, $meth_read:ident) => {
-		impl<W: Writer> Writeable<W> for $val_type {
+		impl Writeable for $val_type {
 			#[inline]
-			fn write(&self, writer: &mut W) -> Result<(), DecodeError> {
+			fn write<W: Writer>(&self, writer: &mut W) -> Result<(), DecodeError> {
 				Ok(writer.write_all(&$meth_write(*self))?)
 			}
 		}
@@ -65,6 +95,8 @@ macro_rules! impl_writeable_primitive {
 				reader.read_exact(&mut buf)?;
 				Ok($meth_read(&buf))
 			}
+
+			const MIN_LENGTH: usize = $len;
 		}
 	}
 }
@@ -73,9 +105,70 @@ impl_writeable_primitive!(u64, be64_to_array, 8, slice_to_be64);
 impl_writeable_primitive!(u32, be32_to_array, 4, slice_to_be32);
 impl_writeable_primitive!(u16, be16_to_array, 2, slice_to_be16);
 
-impl<W: Writer> Writeable<W> for u8 {
+/// Lightning's BigSize variable-length integer, used throughout BOLT #1 and #2 TLV streams.
+/// Unlike a raw u64, this uses the minimal encoding possible for the value: values below 0xfd
+/// are written as a single byte, values up to 0xffff are prefixed with 0xfd, values up to
+/// 0xffffffff are prefixed with 0xfe, and anything larger is prefixed with 0xff. This lets us
+/// encode lengths (and other varying-size values) without either a 64KiB ceiling or wasting
+/// space on small values.
+pub struct BigSize(pub u64);
+impl Writeable for BigSize {
+	#[inline]
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), DecodeError> {
+		match self.0 {
+			0..=0xFC => (self.0 as u8).write(writer),
+			0xFD..=0xFFFF => {
+				writer.write_all(&[0xFDu8])?;
+				(self.0 as u16).write(writer)
+			},
+			0x10000..=0xFFFFFFFF => {
+				writer.write_all(&[0xFEu8])?;
+				(self.0 as u32).write(writer)
+			},
+			_ => {
+				writer.write_all(&[0xFFu8])?;
+				self.0.write(writer)
+			},
+		}
+	}
+}
+impl<R: Read> Readable<R> for BigSize {
+	#[inline]
+	fn read(reader: &mut R) -> Result<BigSize, DecodeError> {
+		let n: u8 = Readable::read(reader)?;
+		match n {
+			0xFF => {
+				let x: u64 = Readable::read(reader)?;
+				if x < 0x100000000 {
+					Err(DecodeError::InvalidValue)
+				} else {
+					Ok(BigSize(x))
+				}
+			}
+			0xFE => {
+				let x: u32 = Readable::read(reader)?;
+				if x < 0x10000 {
+					Err(DecodeError::InvalidValue)
+				} else {
+					Ok(BigSize(x as u64))
+				}
+			}
+			0xFD => {
+				let x: u16 = Readable::read(reader)?;
+				if x < 0xFD {
+					Err(DecodeError::InvalidValue)
+				} else {
+					Ok(BigSize(x as u64))
+				}
+			}
+			n => Ok(BigSize(n as u64))
+		}
+	}
+}
+
+impl Writeable for u8 {
 	#[inline]
-	fn write(&self, writer: &mut W) -> Result<(), DecodeError> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), DecodeError> {
 		Ok(writer.write_all(&[*self])?)
 	}
 }
@@ -86,11 +179,13 @@ impl<R: Read> Readable<R> for u8 {
 		reader.read_exact(&mut buf)?;
 		Ok(buf[0])
 	}
+
+	const MIN_LENGTH: usize = 1;
 }
 
-impl<W: Writer> Writeable<W> for bool {
+impl Writeable for bool {
 	#[inline]
-	fn write(&self, writer: &mut W) -> Result<(), DecodeError> {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), DecodeError> {
 		Ok(writer.write_all(&[if *self {1} else {0}])?)
 	}
 }
@@ -109,10 +204,10 @@ impl<R: Read> Readable<R> for bool {
 // u8 arrays
 macro_rules! impl_array {
 	( $size:expr ) => (
-		impl<W: Writer> Writeable<W> for [u8; $size]
+		impl Writeable for [u8; $size]
 		{
 			#[inline]
-			fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+			fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
 				w.write_all(self)?;
 				Ok(())
 			}
@@ -126,6 +221,8 @@ macro_rules! impl_array {
 				r.read_exact(&mut buf)?;
 				Ok(buf)
 			}
+
+			const MIN_LENGTH: usize = $size;
 		}
 	);
 }
@@ -137,20 +234,25 @@ impl_array!(64); // for Signature
 impl_array!(1300); // for OnionPacket.hop_data
 
 // HashMap
-impl<W, K, V> Writeable<W> for HashMap<K, V>
-	where W: Writer,
-	      K: Writeable<W> + Eq + Hash,
-	      V: Writeable<W>
+impl<K, V> Writeable for HashMap<K, V>
+	where K: Writeable + Eq + Hash,
+	      V: Writeable
 {
 	#[inline]
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
-	(self.len() as u16).write(w)?;
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
+		w.size_hint(self.serialized_length());
+		(self.len() as u16).write(w)?;
 		for (key, value) in self.iter() {
 			key.write(w)?;
 			value.write(w)?;
 		}
 		Ok(())
 	}
+
+	#[inline]
+	fn serialized_length(&self) -> usize {
+		2 + self.iter().map(|(k, v)| k.serialized_length() + v.serialized_length()).sum::<usize>()
+	}
 }
 
 impl<R, K, V> Readable<R> for HashMap<K, V>
@@ -167,65 +269,58 @@ impl<R, K, V> Readable<R> for HashMap<K, V>
 		}
 		Ok(ret)
 	}
+
+	const MIN_LENGTH: usize = 2;
 }
 
 // Vectors
-impl<W: Writer> Writeable<W> for Vec<u8> {
+impl<T: Writeable> Writeable for Vec<T> {
 	#[inline]
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
-		(self.len() as u16).write(w)?;
-		Ok(w.write_all(&self)?)
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
+		w.size_hint(self.serialized_length());
+		BigSize(self.len() as u64).write(w)?;
+		for elem in self.iter() {
+			elem.write(w)?;
+		}
+		Ok(())
 	}
-}
 
-impl<R: Read> Readable<R> for Vec<u8> {
 	#[inline]
-	fn read(r: &mut R) -> Result<Self, DecodeError> {
-		let len: u16 = Readable::read(r)?;
-		let mut ret = Vec::with_capacity(len as usize);
-		ret.resize(len as usize, 0);
-		r.read_exact(&mut ret)?;
-		Ok(ret)
-	}
-}
-impl<W: Writer> Writeable<W> for Vec<Signature> {
-	#[inline]
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
-		let byte_size = (self.len() as usize)
-		                .checked_mul(33)
-		                .ok_or(DecodeError::BadLengthDescriptor)?;
-		if byte_size > MAX_BUF_SIZE {
-			return Err(DecodeError::BadLengthDescriptor);
-		}
-		(self.len() as u16).write(w)?;
-		for e in self.iter() {
-			e.write(w)?;
-		}
-		Ok(())
+	fn serialized_length(&self) -> usize {
+		BigSize(self.len() as u64).serialized_length() +
+			self.iter().map(|elem| elem.serialized_length()).sum::<usize>()
 	}
 }
 
-impl<R: Read> Readable<R> for Vec<Signature> {
+impl<R: Read, T: Readable<R>> Readable<R> for Vec<T> {
 	#[inline]
 	fn read(r: &mut R) -> Result<Self, DecodeError> {
-		let len: u16 = Readable::read(r)?;
-		let byte_size = (len as usize)
-		                .checked_mul(33)
+		let len: BigSize = Readable::read(r)?;
+		let byte_size = (len.0 as usize)
+		                .checked_mul(T::MIN_LENGTH)
 		                .ok_or(DecodeError::BadLengthDescriptor)?;
 		if byte_size > MAX_BUF_SIZE {
 			return Err(DecodeError::BadLengthDescriptor);
 		}
-		let mut ret = Vec::with_capacity(len as usize);
-		for _ in 0..len { ret.push(Signature::read(r)?); }
+		let mut ret = Vec::with_capacity(len.0 as usize);
+		for _ in 0..len.0 {
+			ret.push(T::read(r)?);
+		}
 		Ok(ret)
 	}
 }
 
-impl<W: Writer> Writeable<W> for Script {
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+impl Writeable for Script {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
+		w.size_hint(self.serialized_length());
 		(self.len() as u16).write(w)?;
 		Ok(w.write_all(self.as_bytes())?)
 	}
+
+	#[inline]
+	fn serialized_length(&self) -> usize {
+		2 + self.len()
+	}
 }
 
 impl<R: Read> Readable<R> for Script {
@@ -235,10 +330,12 @@ impl<R: Read> Readable<R> for Script {
 		r.read_exact(&mut buf)?;
 		Ok(Script::from(buf))
 	}
+
+	const MIN_LENGTH: usize = 2;
 }
 
-impl<W: Writer> Writeable<W> for Option<Script> {
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+impl Writeable for Option<Script> {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
 		if let &Some(ref script) = self {
 			script.write(w)?;
 		}
@@ -248,20 +345,101 @@ impl<W: Writer> Writeable<W> for Option<Script> {
 
 impl<R: Read> Readable<R> for Option<Script> {
 	fn read(r: &mut R) -> Result<Self, DecodeError> {
-		match <u16 as Readable<R>>::read(r) {
+		let mut tracking_reader = ReadTrackingReader::new(r);
+		match <u16 as Readable<ReadTrackingReader<&mut R>>>::read(&mut tracking_reader) {
 			Ok(len) => {
 				let mut buf = vec![0; len as usize];
-				r.read_exact(&mut buf)?;
+				tracking_reader.read_exact(&mut buf)?;
 				Ok(Some(Script::from(buf)))
 			},
-			Err(DecodeError::ShortRead) => Ok(None),
+			// A ShortRead before we've read any bytes at all means this trailing, optional
+			// field just wasn't present; a ShortRead after we've started reading means the
+			// stream was genuinely truncated mid-field.
+			Err(DecodeError::ShortRead) if !tracking_reader.have_read => Ok(None),
 			Err(e) => Err(e)
 		}
 	}
 }
 
-impl<W: Writer> Writeable<W> for PublicKey {
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+/// A `Read` which caps reads to a fixed number of bytes ("the budget"), treating any attempt to
+/// read past it as if the stream had ended there. This bounds a nested `Readable::read()` (e.g.
+/// a TLV record's value) so it can never consume bytes belonging to whatever follows it, and it
+/// turns a declared length that exceeds the actual remaining bytes into a clean
+/// `DecodeError::ShortRead` rather than a successful read of unrelated, adjacent data.
+pub struct FixedLengthReader<'a, R: Read + 'a> {
+	read: &'a mut R,
+	bytes_read: u64,
+	total_bytes: u64,
+}
+
+impl<'a, R: Read> FixedLengthReader<'a, R> {
+	/// Constructs a new FixedLengthReader which reads at most `total_bytes` bytes from `read`.
+	pub fn new(read: &'a mut R, total_bytes: u64) -> Self {
+		Self { read, bytes_read: 0, total_bytes }
+	}
+
+	/// Returns whether any bytes within the budget remain unread.
+	pub fn bytes_remain(&mut self) -> bool {
+		self.bytes_read != self.total_bytes
+	}
+
+	/// Reads and discards all bytes remaining within the budget, so that a caller which doesn't
+	/// fully consume a record's value (e.g. an unrecognized odd TLV type) can skip past it while
+	/// leaving the outer stream positioned at the start of the next record. Errors with
+	/// `DecodeError::ShortRead` if the underlying reader ends before the budget is exhausted.
+	pub fn eat_remaining(&mut self) -> Result<(), DecodeError> {
+		::std::io::copy(self, &mut ::std::io::sink()).map_err(|_| DecodeError::ShortRead)?;
+		if self.bytes_read != self.total_bytes {
+			Err(DecodeError::ShortRead)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl<'a, R: Read> Read for FixedLengthReader<'a, R> {
+	fn read(&mut self, dest: &mut [u8]) -> Result<usize, ::std::io::Error> {
+		if self.total_bytes == self.bytes_read {
+			Ok(0)
+		} else {
+			let max_read = ::std::cmp::min(dest.len() as u64, self.total_bytes - self.bytes_read) as usize;
+			let read_len = self.read.read(&mut dest[0..max_read])?;
+			self.bytes_read += read_len as u64;
+			Ok(read_len)
+		}
+	}
+}
+
+/// A `Read` which records whether any bytes at all have been successfully read from it. This
+/// lets a caller distinguish "the stream ended before this optional, trailing field even
+/// started" (not an error) from "the stream ended partway through reading it" (a genuine
+/// `DecodeError::ShortRead`), which catching the error alone can't tell apart.
+pub struct ReadTrackingReader<R: Read> {
+	read: R,
+	/// Whether any bytes have been read from this reader.
+	pub have_read: bool,
+}
+impl<R: Read> ReadTrackingReader<R> {
+	/// Constructs a new ReadTrackingReader wrapping `read`.
+	pub fn new(read: R) -> Self {
+		Self { read, have_read: false }
+	}
+}
+impl<R: Read> Read for ReadTrackingReader<R> {
+	fn read(&mut self, dest: &mut [u8]) -> Result<usize, ::std::io::Error> {
+		match self.read.read(dest) {
+			Ok(0) => Ok(0),
+			Ok(len) => {
+				self.have_read = true;
+				Ok(len)
+			},
+			Err(e) => Err(e),
+		}
+	}
+}
+
+impl Writeable for PublicKey {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
 		self.serialize().write(w)
 	}
 }
@@ -274,10 +452,12 @@ impl<R: Read> Readable<R> for PublicKey {
 			Err(_) => return Err(DecodeError::BadPublicKey),
 		}
 	}
+
+	const MIN_LENGTH: usize = 33;
 }
 
-impl<W: Writer> Writeable<W> for Sha256dHash {
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+impl Writeable for Sha256dHash {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
 		self.as_bytes().write(w)
 	}
 }
@@ -287,10 +467,12 @@ impl<R: Read> Readable<R> for Sha256dHash {
 		let buf: [u8; 32] = Readable::read(r)?;
 		Ok(From::from(&buf[..]))
 	}
+
+	const MIN_LENGTH: usize = 32;
 }
 
-impl<W: Writer> Writeable<W> for Signature {
-	fn write(&self, w: &mut W) -> Result<(), DecodeError> {
+impl Writeable for Signature {
+	fn write<W: Writer>(&self, w: &mut W) -> Result<(), DecodeError> {
 		self.serialize_compact(&Secp256k1::without_caps()).write(w)
 	}
 }
@@ -303,4 +485,287 @@ impl<R: Read> Readable<R> for Signature {
 			Err(_) => return Err(DecodeError::BadSignature),
 		}
 	}
+
+	const MIN_LENGTH: usize = 64;
+}
+
+// TLV format, as defined in BOLT #1, encodes each record as a BigSize type, a BigSize length,
+// and exactly `length` value bytes, with records appearing in strictly increasing type order.
+// Record types are even if the record is mandatory for the reader to understand, and odd if
+// it's OK for the reader to ignore it when it doesn't recognize the type.
+
+/// Writes out a single TLV record, given its type number and already-Writeable value, to the
+/// given Writer, as the BigSize type, the BigSize length of the encoded value, and then the
+/// value itself.
+#[doc(hidden)]
+pub fn write_tlv_field<W: Writer, T: Writeable>(writer: &mut W, ty: u64, val: &T) -> Result<(), DecodeError> {
+	BigSize(ty).write(writer)?;
+	BigSize(val.serialized_length() as u64).write(writer)?;
+	val.write(writer)
+}
+
+/// Encodes the given set of TLV fields, each either `required` or `option`al, into `$stream` in
+/// the order they are listed; the fields are neither sorted nor checked for increasing type
+/// order. `option` fields whose value is `None` are omitted entirely.
+///
+/// ```ignore
+/// encode_tlv_stream!(stream, {
+/// 	(0, self.channel_id, required),
+/// 	(2, self.short_channel_id, option),
+/// });
+/// ```
+#[macro_export]
+macro_rules! encode_tlv_stream {
+	($stream: expr, {$(($type: expr, $field: expr, $fieldty: tt)),* $(,)*}) => {
+		$(
+			$crate::_encode_tlv!($stream, $type, $field, $fieldty);
+		)*
+	}
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _encode_tlv {
+	($stream: expr, $type: expr, $field: expr, required) => {
+		$crate::util::ser::write_tlv_field($stream, $type, &$field)?;
+	};
+	($stream: expr, $type: expr, $field: expr, option) => {
+		if let Some(ref field) = $field {
+			$crate::util::ser::write_tlv_field($stream, $type, field)?;
+		}
+	};
+}
+
+/// Reads a TLV stream from `$stream` until EOF, dispatching each record's value bytes to the
+/// matching `required`/`option` field by type number. Record types must appear in strictly
+/// increasing order; a duplicate or out-of-order type is a hard decode error. A record whose
+/// type isn't declared here is a hard error if its type is even (the reader is required to
+/// understand it) and is otherwise skipped.
+///
+/// ```ignore
+/// decode_tlv_stream!(stream, {
+/// 	(0, channel_id, required),
+/// 	(2, short_channel_id, option),
+/// });
+/// ```
+#[macro_export]
+macro_rules! decode_tlv_stream {
+	($stream: expr, {$(($type: expr, $field: ident, $fieldty: tt)),* $(,)*}) => {
+		let mut last_seen_type: Option<u64> = None;
+		$(let mut $field = None;)*
+		loop {
+			let typ: $crate::util::ser::BigSize = match Readable::read($stream) {
+				Ok(t) => t,
+				Err(DecodeError::ShortRead) => break,
+				Err(e) => return Err(e),
+			};
+			if let Some(last_type) = last_seen_type {
+				if typ.0 <= last_type {
+					return Err(DecodeError::InvalidValue);
+				}
+			}
+			last_seen_type = Some(typ.0);
+			let length: $crate::util::ser::BigSize = Readable::read($stream)?;
+			let mut value_reader = $crate::util::ser::FixedLengthReader::new($stream, length.0);
+			match typ.0 {
+				$($type => { $field = Some(Readable::read(&mut value_reader)?); },)*
+				_ => {
+					if typ.0 % 2 == 0 {
+						return Err(DecodeError::InvalidValue);
+					}
+				}
+			}
+			value_reader.eat_remaining()?;
+		}
+		$(
+			$crate::_check_decoded_tlv!($field, $fieldty);
+		)*
+	}
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _check_decoded_tlv {
+	($field: ident, required) => {
+		let $field = match $field {
+			Some(val) => val,
+			None => return Err(DecodeError::InvalidValue),
+		};
+	};
+	($field: ident, option) => {};
+}
+
+// Versioned framing for persisted objects (channel/monitor state, etc). Unlike the TLV streams
+// above, which describe the optional/extensible tail of a single message, this describes the
+// whole serialized object: a newer writer can freely append extra fields after the object's
+// original fields (including a TLV stream) and bump $ser_ver, while still setting
+// $min_enc_version to an older value that readers which don't understand the new fields can
+// stop after reading the original fields. Only when the
+// object's encoding has changed in a way that's genuinely unreadable by old code does
+// $min_enc_version need to be bumped, at which point old readers hard-fail instead of silently
+// misinterpreting the data.
+
+/// Writes a two-byte version prefix, to go ahead of an object's own serialization: `$ser_ver`, the
+/// serialization version this object is being written as, and `$min_enc_version`, the oldest
+/// serialization version of this code that can still read the result. Pair with
+/// `read_ver_prefix!` on the decoding side.
+#[macro_export]
+macro_rules! write_ver_prefix {
+	($stream: expr, $ser_ver: expr, $min_enc_version: expr) => {
+		$stream.write_all(&[$ser_ver, $min_enc_version])?;
+	}
+}
+
+/// Reads a two-byte version prefix written by `write_ver_prefix!`, returning the serialization
+/// version the object was written with. Errors with `DecodeError::UnknownVersion` if the
+/// object's declared minimum-readable version is newer than `$max_ver`, the newest version this
+/// code understands, meaning the object is genuinely too new for us to interpret.
+#[macro_export]
+macro_rules! read_ver_prefix {
+	($stream: expr, $max_ver: expr) => { {
+		let ser_ver: u8 = Readable::read($stream)?;
+		let min_ver: u8 = Readable::read($stream)?;
+		if min_ver > $max_ver {
+			return Err(DecodeError::UnknownVersion);
+		}
+		ser_ver
+	} }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	#[test]
+	fn bigsize_encoding_round_trips_and_is_minimal() {
+		let cases: &[(u64, usize)] = &[
+			(0, 1),
+			(0xFC, 1),
+			(0xFD, 3),
+			(0xFFFF, 3),
+			(0x10000, 5),
+			(0xFFFFFFFF, 5),
+			(0x100000000, 9),
+			(u64::MAX, 9),
+		];
+		for &(value, expected_len) in cases {
+			let mut buf = Vec::new();
+			BigSize(value).write(&mut buf).unwrap();
+			assert_eq!(buf.len(), expected_len);
+			let mut cursor = Cursor::new(buf);
+			let decoded: BigSize = Readable::read(&mut cursor).unwrap();
+			assert_eq!(decoded.0, value);
+		}
+	}
+
+	#[test]
+	fn bigsize_rejects_non_minimal_encodings() {
+		// 0xfd prefix encoding a value which fits in a single byte.
+		let res: Result<BigSize, _> = Readable::read(&mut Cursor::new(vec![0xfd, 0x00, 0xfc]));
+		assert!(matches!(res, Err(DecodeError::InvalidValue)));
+		// 0xfe prefix encoding a value which fits in the 0xfd form.
+		let res: Result<BigSize, _> = Readable::read(&mut Cursor::new(vec![0xfe, 0x00, 0x00, 0xff, 0xff]));
+		assert!(matches!(res, Err(DecodeError::InvalidValue)));
+		// 0xff prefix encoding a value which fits in the 0xfe form.
+		let res: Result<BigSize, _> = Readable::read(&mut Cursor::new(vec![0xff, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]));
+		assert!(matches!(res, Err(DecodeError::InvalidValue)));
+	}
+
+	#[test]
+	fn tlv_stream_round_trips_required_and_optional_fields() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 0, &42u64).unwrap();
+		write_tlv_field(&mut buf, 2, &9u32).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<(u64, Option<u32>), DecodeError> {
+			decode_tlv_stream!(stream, {
+				(0, required_field, required),
+				(2, optional_field, option),
+			});
+			Ok((required_field, optional_field))
+		};
+		assert_eq!(decode(&mut cursor).unwrap(), (42, Some(9)));
+	}
+
+	#[test]
+	fn tlv_stream_errors_when_required_field_missing() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 2, &9u32).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<(u64, Option<u32>), DecodeError> {
+			decode_tlv_stream!(stream, {
+				(0, required_field, required),
+				(2, optional_field, option),
+			});
+			Ok((required_field, optional_field))
+		};
+		assert!(matches!(decode(&mut cursor), Err(DecodeError::InvalidValue)));
+	}
+
+	#[test]
+	fn tlv_stream_rejects_duplicate_types() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 0, &42u64).unwrap();
+		write_tlv_field(&mut buf, 0, &43u64).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<u64, DecodeError> {
+			decode_tlv_stream!(stream, {
+				(0, required_field, required),
+			});
+			Ok(required_field)
+		};
+		assert!(matches!(decode(&mut cursor), Err(DecodeError::InvalidValue)));
+	}
+
+	#[test]
+	fn tlv_stream_rejects_out_of_order_types() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 2, &9u32).unwrap();
+		write_tlv_field(&mut buf, 0, &42u64).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<(u64, Option<u32>), DecodeError> {
+			decode_tlv_stream!(stream, {
+				(0, required_field, required),
+				(2, optional_field, option),
+			});
+			Ok((required_field, optional_field))
+		};
+		assert!(matches!(decode(&mut cursor), Err(DecodeError::InvalidValue)));
+	}
+
+	#[test]
+	fn tlv_stream_hard_fails_on_unknown_even_type() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 4, &42u64).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<Option<u64>, DecodeError> {
+			decode_tlv_stream!(stream, {
+				(0, known_field, option),
+			});
+			Ok(known_field)
+		};
+		assert!(matches!(decode(&mut cursor), Err(DecodeError::InvalidValue)));
+	}
+
+	#[test]
+	fn tlv_stream_skips_unknown_odd_type() {
+		let mut buf = Vec::new();
+		write_tlv_field(&mut buf, 1, &42u64).unwrap();
+		write_tlv_field(&mut buf, 2, &7u32).unwrap();
+
+		let mut cursor = Cursor::new(buf);
+		let decode = |stream: &mut Cursor<Vec<u8>>| -> Result<Option<u32>, DecodeError> {
+			decode_tlv_stream!(stream, {
+				(2, known_field, option),
+			});
+			Ok(known_field)
+		};
+		assert_eq!(decode(&mut cursor).unwrap(), Some(7));
+	}
 }
\ No newline at end of file